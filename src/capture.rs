@@ -0,0 +1,125 @@
+use libc::{pid_t, uid_t};
+use tar::{Archive, Builder, Header};
+use users::User;
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use super::error::Error;
+
+/// An in-memory replay of a `--capture`d tar archive, so a snapshot of
+/// `/proc` can be sorted, filtered and printed offline or on another host.
+pub struct Snapshot {
+    entries: HashMap<String, Vec<u8>>,
+}
+
+impl Snapshot {
+    pub fn is_archive(source: &Path) -> bool {
+        source.is_file()
+    }
+
+    pub fn load(source: &Path) -> Result<Self, Error> {
+        let mut archive = Archive::new(File::open(source)?);
+        let mut entries = HashMap::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.to_string_lossy().into_owned();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            entries.insert(name, data);
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn read(&self, name: &str) -> Option<&[u8]> {
+        self.entries.get(name).map(Vec::as_slice)
+    }
+
+    pub fn pids(&self) -> Vec<pid_t> {
+        let mut pids: Vec<pid_t> = self
+            .entries
+            .keys()
+            .filter_map(|k| k.split('/').next())
+            .filter_map(|p| p.parse().ok())
+            .collect();
+        pids.sort_unstable();
+        pids.dedup();
+        pids
+    }
+
+    pub fn users(&self) -> HashMap<uid_t, User> {
+        let mut users = HashMap::new();
+        if let Some(passwd) = self.read("passwd") {
+            for line in passwd.split(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(line);
+                let mut fields = line.splitn(4, ':');
+                let name = fields.next().unwrap_or_default();
+                let uid = fields.nth(1).and_then(|s| s.parse::<uid_t>().ok());
+                if let (false, Some(uid)) = (name.is_empty(), uid) {
+                    users.insert(uid, User::new(uid, name, 0));
+                }
+            }
+        }
+        users
+    }
+}
+
+/// Appends `path` to `builder` under `name`, if it can be opened. Reads the
+/// file into memory first instead of using `Builder::append_file`: procfs
+/// files report a `stat` size of 0, and `append_file` trusts that size when
+/// writing the tar header, corrupting the archive as soon as it holds more
+/// than one entry.
+fn append_proc_file(builder: &mut Builder<File>, path: &Path, name: &str) -> Result<(), Error> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Ok(()),
+    };
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+
+    let mut header = Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, name, &data[..])?;
+    Ok(())
+}
+
+/// Walks `source` (normally `/proc`) and archives the files `run` needs to
+/// replay it later: each process's `comm`, `cmdline`, `status`, `smaps` and
+/// `smaps_rollup`, plus `meminfo` and `/etc/passwd` for user resolution.
+/// Both smaps variants are stored (rather than just the rollup, when
+/// present): `--map` replay needs the full per-mapping `smaps` breakdown,
+/// while the process/group views prefer the cheaper rollup when reading
+/// live from `/proc`.
+pub fn capture(source: &Path, dest: &Path) -> Result<(), Error> {
+    let mut builder = Builder::new(File::create(dest)?);
+
+    append_proc_file(&mut builder, &source.join("meminfo"), "meminfo")?;
+    append_proc_file(&mut builder, Path::new("/etc/passwd"), "passwd")?;
+
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let is_dir = match entry.metadata() {
+            Ok(metadata) => metadata.is_dir(),
+            Err(_) => continue,
+        };
+        if !is_dir {
+            continue;
+        }
+        let pid_dir = entry.path();
+        let pid = match pid_dir.file_name().and_then(|n| n.to_str()) {
+            Some(pid) if pid.chars().all(|c| c.is_ascii_digit()) => pid,
+            _ => continue,
+        };
+
+        for name in &["comm", "cmdline", "status", "smaps", "smaps_rollup"] {
+            append_proc_file(&mut builder, &pid_dir.join(name), &format!("{}/{}", pid, name))?;
+        }
+    }
+
+    builder.finish()?;
+    Ok(())
+}
@@ -19,8 +19,8 @@ impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::Io(e) => e.fmt(f),
-            Error::Processing(_) => self.fmt(f),
-            Error::ParseSize => self.fmt(f),
+            Error::Processing(s) => write!(f, "{}", s),
+            Error::ParseSize => write!(f, "could not parse size"),
         }
     }
 }
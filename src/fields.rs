@@ -13,6 +13,19 @@ pub enum Field {
     Rss,
     Uss,
     Swap,
+    PssPercent,
+    RssPercent,
+    SwapPercent,
+    Shared,
+    PssAnon,
+    PssFile,
+    Referenced,
+    Anonymous,
+    Locked,
+    PrivateHugetlb,
+    Count,
+    Mapping,
+    Mappers,
     Cmdline,
 }
 
@@ -23,6 +36,15 @@ pub enum FieldKind {
     Text,
 }
 
+/// The raw value of a field, independent of the table/csv/json output format.
+/// Sizes are always carried in bytes here; human-readable abbreviation is a
+/// concern of the table renderer only.
+pub enum FieldValue {
+    Text(String),
+    Integer(u64),
+    Float(f64),
+}
+
 impl Field {
     pub fn name(self) -> &'static str {
         match self {
@@ -32,14 +54,54 @@ impl Field {
             Field::Rss => "Rss",
             Field::Uss => "Uss",
             Field::Swap => "Swap",
+            Field::PssPercent => "Pss%",
+            Field::RssPercent => "Rss%",
+            Field::SwapPercent => "Swap%",
+            Field::Shared => "Shared",
+            Field::PssAnon => "PssAnon",
+            Field::PssFile => "PssFile",
+            Field::Referenced => "Referenced",
+            Field::Anonymous => "Anonymous",
+            Field::Locked => "Locked",
+            Field::PrivateHugetlb => "PrivateHugetlb",
+            Field::Count => "Count",
+            Field::Mapping => "Mapping",
+            Field::Mappers => "Mappers",
             Field::Cmdline => "Cmdline",
         }
     }
 
+    /// Whether `self` can be shown/sorted on in the view `opts` selects
+    /// (plain process list, `--group-by`, or `--map`). Columns are
+    /// validated against this up front so an incompatible combination is
+    /// reported as an error instead of panicking deep in `format_field`.
+    pub fn supported(self, opts: &Options) -> bool {
+        match self {
+            Field::Pid => !opts.map && opts.group_by.is_none(),
+            Field::User | Field::Cmdline => !opts.map,
+            Field::Count => opts.group_by.is_some(),
+            Field::Mapping | Field::Mappers => opts.map,
+            _ => true,
+        }
+    }
+
     pub fn kind(self, opts: &Options) -> FieldKind {
         match self {
-            Field::Pid => FieldKind::Id,
-            Field::Pss | Field::Rss | Field::Uss | Field::Swap => FieldKind::Size,
+            Field::Pid | Field::Count | Field::Mappers => FieldKind::Id,
+            Field::Pss
+            | Field::Rss
+            | Field::Uss
+            | Field::Swap
+            | Field::PssPercent
+            | Field::RssPercent
+            | Field::SwapPercent
+            | Field::Shared
+            | Field::PssAnon
+            | Field::PssFile
+            | Field::Referenced
+            | Field::Anonymous
+            | Field::Locked
+            | Field::PrivateHugetlb => FieldKind::Size,
             Field::User => {
                 if opts.numeric {
                     FieldKind::Id
@@ -47,7 +109,7 @@ impl Field {
                     FieldKind::Text
                 }
             }
-            Field::Cmdline => FieldKind::Text,
+            Field::Mapping | Field::Cmdline => FieldKind::Text,
         }
     }
 }
@@ -63,6 +125,19 @@ impl FromStr for Field {
             "rss" => Ok(Field::Rss),
             "uss" => Ok(Field::Uss),
             "swap" => Ok(Field::Swap),
+            "psspercent" => Ok(Field::PssPercent),
+            "rsspercent" => Ok(Field::RssPercent),
+            "swappercent" => Ok(Field::SwapPercent),
+            "shared" => Ok(Field::Shared),
+            "pssanon" => Ok(Field::PssAnon),
+            "pssfile" => Ok(Field::PssFile),
+            "referenced" => Ok(Field::Referenced),
+            "anonymous" => Ok(Field::Anonymous),
+            "locked" => Ok(Field::Locked),
+            "privatehugetlb" => Ok(Field::PrivateHugetlb),
+            "count" => Ok(Field::Count),
+            "mapping" => Ok(Field::Mapping),
+            "mappers" => Ok(Field::Mappers),
             "cmdline" => Ok(Field::Cmdline),
             _ => Err(format!("Unknown field: {}", s)),
         }
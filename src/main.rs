@@ -6,26 +6,69 @@ use users::User;
 
 use std::collections::HashMap;
 use std::ffi::OsString;
-use std::fs::{self, File};
+use std::fs;
 use std::io::{self, BufRead, BufReader};
 use std::os::unix::prelude::OsStringExt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+use self::capture::Snapshot;
 use self::error::Error;
 use self::fields::{Field, FieldKind};
-use self::options::Options;
-use self::stats::{ProcessDetails, ProcessSizes};
+use self::options::{GroupBy, Options, OutputFormat};
+use self::stats::{GroupDetails, Mapping, ProcessDetails, ProcessSizes};
 
+mod capture;
 mod error;
 mod fields;
 mod filter;
 mod options;
+mod output;
 mod stats;
 
-fn all_users() -> HashMap<uid_t, User> {
-    unsafe { users::all_users() }
-        .map(|u| (u.uid(), u))
-        .collect()
+fn read_source(path: &Path, snapshot: Option<&Snapshot>) -> Result<Vec<u8>, Error> {
+    match snapshot {
+        Some(snapshot) => snapshot.read(&path.to_string_lossy()).map(<[u8]>::to_vec).ok_or_else(|| {
+            Error::Processing(format!("`{}' not found in snapshot", path.display()))
+        }),
+        None => Ok(fs::read(path)?),
+    }
+}
+
+fn open_reader(path: &Path, snapshot: Option<&Snapshot>) -> Result<BufReader<io::Cursor<Vec<u8>>>, Error> {
+    Ok(BufReader::new(io::Cursor::new(read_source(path, snapshot)?)))
+}
+
+fn all_users(snapshot: Option<&Snapshot>) -> HashMap<uid_t, User> {
+    match snapshot {
+        Some(snapshot) => snapshot.users(),
+        None => unsafe { users::all_users() }
+            .map(|u| (u.uid(), u))
+            .collect(),
+    }
+}
+
+// `/proc/meminfo` entries look like `MemTotal:       16369876 kB`.
+fn parse_meminfo_value(line: &str) -> u64 {
+    line.split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or_default()
+        * 1024
+}
+
+fn read_meminfo_totals(source: &Path, snapshot: Option<&Snapshot>) -> (u64, u64) {
+    let mut mem_total = 0;
+    let mut swap_total = 0;
+    if let Ok(reader) = open_reader(&source.join("meminfo"), snapshot) {
+        for line in reader.lines().flatten() {
+            if line.starts_with("MemTotal:") {
+                mem_total = parse_meminfo_value(&line);
+            } else if line.starts_with("SwapTotal:") {
+                swap_total = parse_meminfo_value(&line);
+            }
+        }
+    }
+    (mem_total, swap_total)
 }
 
 fn parse_uid(s: &str) -> uid_t {
@@ -37,9 +80,9 @@ fn parse_uid(s: &str) -> uid_t {
         .unwrap_or_default()
 }
 
-fn get_process_uid(path: &Path) -> Result<uid_t, Error> {
+fn get_process_uid(path: &Path, snapshot: Option<&Snapshot>) -> Result<uid_t, Error> {
     let mut line = String::new();
-    let mut reader = BufReader::new(File::open(path.join("status"))?);
+    let mut reader = open_reader(&path.join("status"), snapshot)?;
     while reader.read_line(&mut line).unwrap_or_default() > 0 {
         if line.starts_with("Uid:") {
             return Ok(parse_uid(&line));
@@ -59,14 +102,14 @@ fn get_process_id(path: &Path) -> Result<pid_t, Error> {
         .ok_or_else(|| Error::Processing("Failed to get PID".to_owned()))
 }
 
-fn get_process_command(path: &Path) -> Result<OsString, Error> {
-    let mut command = fs::read(path.join("comm"))?;
+fn get_process_command(path: &Path, snapshot: Option<&Snapshot>) -> Result<OsString, Error> {
+    let mut command = read_source(&path.join("comm"), snapshot)?;
     command.pop();
     Ok(OsString::from_vec(command))
 }
 
-fn get_cmdline(path: &Path) -> Result<OsString, Error> {
-    let mut cmdline = fs::read(path.join("cmdline"))?;
+fn get_cmdline(path: &Path, snapshot: Option<&Snapshot>) -> Result<OsString, Error> {
+    let mut cmdline = read_source(&path.join("cmdline"), snapshot)?;
     for c in &mut cmdline {
         if *c == b'\0' {
             *c = b' ';
@@ -78,42 +121,103 @@ fn get_cmdline(path: &Path) -> Result<OsString, Error> {
     Ok(OsString::from_vec(cmdline))
 }
 
-fn open_smaps(path: &Path) -> io::Result<BufReader<File>> {
-    let file = match File::open(path.join("smaps_rollup")) {
-        Ok(file) => file,
-        Err(_) => File::open(path.join("smaps"))?,
-    };
-    Ok(BufReader::new(file))
+fn open_smaps(
+    path: &Path,
+    snapshot: Option<&Snapshot>,
+) -> Result<BufReader<io::Cursor<Vec<u8>>>, Error> {
+    match open_reader(&path.join("smaps_rollup"), snapshot) {
+        Ok(reader) => Ok(reader),
+        Err(_) => open_reader(&path.join("smaps"), snapshot),
+    }
+}
+
+/// Folds one line of an smaps(_rollup) entry into `sizes`. Shared by
+/// `get_memory_info` (totals for a whole process) and `get_mappings`
+/// (totals per mapping within a process), which both need the full field
+/// set so `--columns` reports the same data regardless of view.
+fn accumulate_smap_line(sizes: &mut ProcessSizes, line: &str) -> Result<(), Error> {
+    if line.starts_with("Pss:") {
+        sizes.pss += Size::from_smap_entry(line)?;
+    } else if line.starts_with("Rss:") {
+        sizes.rss += Size::from_smap_entry(line)?;
+    } else if line.starts_with("Private_Clean:") || line.starts_with("Private_Dirty:") {
+        sizes.uss += Size::from_smap_entry(line)?;
+    } else if line.starts_with("Swap:") {
+        sizes.swap += Size::from_smap_entry(line)?;
+    } else if line.starts_with("Shared_Clean:") || line.starts_with("Shared_Dirty:") {
+        sizes.shared += Size::from_smap_entry(line)?;
+    } else if line.starts_with("Pss_Anon:") {
+        sizes.pss_anon += Size::from_smap_entry(line)?;
+    } else if line.starts_with("Pss_File:") {
+        sizes.pss_file += Size::from_smap_entry(line)?;
+    } else if line.starts_with("Referenced:") {
+        sizes.referenced += Size::from_smap_entry(line)?;
+    } else if line.starts_with("Anonymous:") {
+        sizes.anonymous += Size::from_smap_entry(line)?;
+    } else if line.starts_with("Locked:") {
+        sizes.locked += Size::from_smap_entry(line)?;
+    } else if line.starts_with("Private_Hugetlb:") {
+        sizes.private_hugetlb += Size::from_smap_entry(line)?;
+    }
+    Ok(())
 }
 
-fn get_memory_info(path: &Path) -> Result<ProcessSizes, Error> {
-    let mut reader = open_smaps(path)?;
+fn get_memory_info(path: &Path, snapshot: Option<&Snapshot>) -> Result<ProcessSizes, Error> {
+    let mut reader = open_smaps(path, snapshot)?;
     let mut sizes: ProcessSizes = Default::default();
     let mut line = String::new();
 
     while reader.read_line(&mut line).unwrap_or_default() > 0 {
-        if line.starts_with("Pss:") {
-            sizes.pss += Size::from_smap_entry(&line)?;
-        } else if line.starts_with("Rss:") {
-            sizes.rss += Size::from_smap_entry(&line)?;
-        } else if line.starts_with("Private_Clean:") || line.starts_with("Private_Dirty:") {
-            sizes.uss += Size::from_smap_entry(&line)?;
-        } else if line.starts_with("Swap:") {
-            sizes.swap += Size::from_smap_entry(&line)?;
+        accumulate_smap_line(&mut sizes, &line)?;
+        line.clear();
+    }
+
+    Ok(sizes)
+}
+
+fn is_mapping_header(line: &str) -> bool {
+    line.as_bytes().first().map_or(false, u8::is_ascii_hexdigit)
+        && line.splitn(2, ' ').next().unwrap_or_default().contains('-')
+}
+
+fn parse_mapping_name(line: &str) -> OsString {
+    let path = line.split_whitespace().skip(5).collect::<Vec<_>>().join(" ");
+    if path.is_empty() {
+        OsString::from("[anon]")
+    } else {
+        OsString::from(path)
+    }
+}
+
+fn get_mappings(
+    path: &Path,
+    snapshot: Option<&Snapshot>,
+) -> Result<HashMap<OsString, ProcessSizes>, Error> {
+    let mut reader = open_reader(&path.join("smaps"), snapshot)?;
+    let mut mappings: HashMap<OsString, ProcessSizes> = HashMap::new();
+    let mut current: Option<OsString> = None;
+    let mut line = String::new();
+
+    while reader.read_line(&mut line).unwrap_or_default() > 0 {
+        if is_mapping_header(&line) {
+            current = Some(parse_mapping_name(&line));
+        } else if let Some(ref name) = current {
+            let sizes = mappings.entry(name.clone()).or_insert_with(Default::default);
+            accumulate_smap_line(sizes, &line)?;
         }
 
         line.clear();
     }
 
-    Ok(sizes)
+    Ok(mappings)
 }
 
-fn get_process(path: &Path) -> Result<Process, Error> {
+fn get_process(path: &Path, snapshot: Option<&Snapshot>) -> Result<Process, Error> {
     Ok(Process {
         pid: get_process_id(path)?,
-        uid: get_process_uid(path)?,
-        command: get_process_command(path)?,
-        cmdline: get_cmdline(path)?,
+        uid: get_process_uid(path, snapshot)?,
+        command: get_process_command(path, snapshot)?,
+        cmdline: get_cmdline(path, snapshot)?,
         procfs_path: path.to_path_buf(),
     })
 }
@@ -121,11 +225,12 @@ fn get_process(path: &Path) -> Result<Process, Error> {
 fn get_process_details(
     process: &Process,
     users: &HashMap<uid_t, User>,
+    snapshot: Option<&Snapshot>,
 ) -> Result<ProcessDetails, Error> {
     let user = users
         .get(&process.uid)
         .ok_or_else(|| Error::Processing("Could not get user name".to_owned()))?;
-    let sizes = get_memory_info(&process.procfs_path)?;
+    let sizes = get_memory_info(&process.procfs_path, snapshot)?;
     let statistics = ProcessDetails {
         process: process.clone(),
         user: user.clone(),
@@ -134,37 +239,76 @@ fn get_process_details(
     Ok(statistics)
 }
 
-fn all_processes(path: &Path) -> Vec<Process> {
-    fs::read_dir(path)
-        .unwrap_or_else(|e| panic!("can't read {}: {}", path.display(), e))
-        .filter_map(|e| e.ok())
-        .collect::<Vec<_>>()
-        .par_iter()
-        .map(|e| match e.metadata() {
-            Ok(m) if m.is_dir() => get_process(&e.path()).ok(),
-            _ => None,
-        })
-        .flatten()
-        .collect::<Vec<_>>()
+fn all_processes(path: &Path, snapshot: Option<&Snapshot>) -> Vec<Process> {
+    match snapshot {
+        Some(snapshot) => snapshot
+            .pids()
+            .par_iter()
+            .filter_map(|&pid| get_process(&PathBuf::from(pid.to_string()), Some(snapshot)).ok())
+            .collect(),
+        None => fs::read_dir(path)
+            .unwrap_or_else(|e| panic!("can't read {}: {}", path.display(), e))
+            .filter_map(|e| e.ok())
+            .collect::<Vec<_>>()
+            .par_iter()
+            .map(|e| match e.metadata() {
+                Ok(m) if m.is_dir() => get_process(&e.path(), None).ok(),
+                _ => None,
+            })
+            .flatten()
+            .collect::<Vec<_>>(),
+    }
 }
 
-fn print_processes(process_details: Vec<ProcessDetails>, options: &Options) -> Result<(), Error> {
-    let default_fields = vec![
-        Field::Pid,
-        Field::User,
-        Field::Pss,
-        Field::Rss,
-        Field::Uss,
-        Field::Swap,
-        Field::Cmdline,
-    ];
-
-    let active_fields = if options.fields.is_empty() {
-        &default_fields
-    } else {
-        &options.fields
-    };
+fn print_rows<T>(
+    rows: &[T],
+    active_fields: &[Field],
+    options: &Options,
+    field_value: impl Fn(&T, Field, &Options) -> fields::FieldValue,
+) -> Result<(), Error> {
+    match options.format {
+        OutputFormat::Csv => {
+            if !options.no_header {
+                output::csv_header(io::stdout(), active_fields)?;
+            }
+            for row in rows {
+                let values = active_fields
+                    .iter()
+                    .map(|&f| field_value(row, f, options))
+                    .collect::<Vec<_>>();
+                output::csv_row(io::stdout(), &values)?;
+            }
+        }
+        OutputFormat::Json => {
+            print!("[");
+            for (i, row) in rows.iter().enumerate() {
+                if i > 0 {
+                    print!(",");
+                }
+                let values = active_fields
+                    .iter()
+                    .map(|&f| field_value(row, f, options))
+                    .collect::<Vec<_>>();
+                output::json_row(io::stdout(), active_fields, &values)?;
+            }
+            println!("]");
+        }
+        OutputFormat::Table => unreachable!(),
+    }
+    Ok(())
+}
 
+/// Renders `rows` as a table: header line, one row per entry, and an
+/// optional totals line. Shared by the per-process, `--group-by` and
+/// `--map` views, which otherwise only differ in their field set and how
+/// a row is formatted/summed.
+fn print_table<T>(
+    rows: Vec<T>,
+    active_fields: &[Field],
+    options: &Options,
+    format_field: impl Fn(&T, io::Stdout, Field, &Options, &FileSizeOpts) -> io::Result<()>,
+    into_sizes: impl Fn(T) -> ProcessSizes,
+) -> Result<(), Error> {
     if !options.no_header {
         for c in active_fields {
             if c.kind(options) == FieldKind::Text {
@@ -179,18 +323,20 @@ fn print_processes(process_details: Vec<ProcessDetails>, options: &Options) -> R
         space: false,
         ..CONVENTIONAL
     };
-    let mut totals: ProcessSizes = Default::default();
-    for details in process_details {
+    for row in &rows {
         for &c in active_fields {
-            details
-                .format_field(io::stdout(), c, options, &file_size_opts)
-                .unwrap();
+            format_field(row, io::stdout(), c, options, &file_size_opts).unwrap();
             print!(" ");
         }
         println!();
-        totals += details.sizes;
     }
     if options.totals {
+        let totals = rows
+            .into_iter()
+            .fold(ProcessSizes::default(), |mut totals, row| {
+                totals += into_sizes(row);
+                totals
+            });
         println!(
             "--------------------------------------------------------------------------------"
         );
@@ -209,9 +355,185 @@ fn print_processes(process_details: Vec<ProcessDetails>, options: &Options) -> R
     Ok(())
 }
 
-fn run(options: &Options) -> Result<(), Error> {
-    let users = all_users();
-    let processes = all_processes(&options.source);
+fn print_processes(process_details: Vec<ProcessDetails>, options: &Options) -> Result<(), Error> {
+    let default_fields = vec![
+        Field::Pid,
+        Field::User,
+        Field::Pss,
+        Field::Rss,
+        Field::Uss,
+        Field::Swap,
+        Field::Cmdline,
+    ];
+
+    let active_fields = if options.fields.is_empty() {
+        &default_fields
+    } else {
+        &options.fields
+    };
+
+    if options.format != OutputFormat::Table {
+        return print_rows(&process_details, active_fields, options, |d, f, o| {
+            d.field_value(f, o)
+        });
+    }
+
+    print_table(
+        process_details,
+        active_fields,
+        options,
+        |d, w, f, o, so| d.format_field(w, f, o, so),
+        |d| d.sizes,
+    )
+}
+
+fn group_key(details: &ProcessDetails, group_by: GroupBy) -> OsString {
+    match group_by {
+        GroupBy::User => details.user.name().to_os_string(),
+        GroupBy::Command => details.process.command.clone(),
+    }
+}
+
+fn group_processes(process_details: Vec<ProcessDetails>, group_by: GroupBy) -> Vec<GroupDetails> {
+    let mut groups: HashMap<OsString, (usize, ProcessSizes)> = HashMap::new();
+    for details in process_details {
+        let key = group_key(&details, group_by);
+        let group = groups.entry(key).or_insert_with(Default::default);
+        group.0 += 1;
+        group.1 += details.sizes;
+    }
+    groups
+        .into_iter()
+        .map(|(key, (count, sizes))| GroupDetails { key, count, sizes })
+        .collect()
+}
+
+fn print_groups(group_details: Vec<GroupDetails>, options: &Options) -> Result<(), Error> {
+    let key_field = match options.group_by {
+        Some(GroupBy::Command) => Field::Cmdline,
+        _ => Field::User,
+    };
+    let default_fields = vec![key_field, Field::Count, Field::Pss, Field::Rss, Field::Uss];
+
+    let active_fields = if options.fields.is_empty() {
+        &default_fields
+    } else {
+        &options.fields
+    };
+
+    if options.format != OutputFormat::Table {
+        return print_rows(&group_details, active_fields, options, |g, f, o| {
+            g.field_value(f, o)
+        });
+    }
+
+    print_table(
+        group_details,
+        active_fields,
+        options,
+        |g, w, f, o, so| g.format_field(w, f, o, so),
+        |g| g.sizes,
+    )
+}
+
+fn group_mappings(
+    processes: &[Process],
+    filters: &filter::Filters,
+    users: &HashMap<uid_t, User>,
+    snapshot: Option<&Snapshot>,
+) -> Vec<Mapping> {
+    let mut mappings: HashMap<OsString, Mapping> = HashMap::new();
+    for process in processes {
+        if !filters.accept_process(&process.command) && !filters.accept_process(&process.cmdline)
+        {
+            continue;
+        }
+        let user = match users.get(&process.uid) {
+            Some(user) => user,
+            None => continue,
+        };
+        if !filters.accept_user(user.name()) {
+            continue;
+        }
+        let process_mappings = match get_mappings(&process.procfs_path, snapshot) {
+            Ok(process_mappings) => process_mappings,
+            Err(_) => continue,
+        };
+        for (name, sizes) in process_mappings {
+            let mapping = mappings.entry(name.clone()).or_insert_with(|| Mapping {
+                name,
+                mapper_count: 0,
+                sizes: Default::default(),
+            });
+            mapping.mapper_count += 1;
+            mapping.sizes += sizes;
+        }
+    }
+    mappings.into_iter().map(|(_, mapping)| mapping).collect()
+}
+
+fn print_mappings(mappings: Vec<Mapping>, options: &Options) -> Result<(), Error> {
+    let default_fields = vec![Field::Mapping, Field::Mappers, Field::Pss, Field::Rss, Field::Uss];
+
+    let active_fields = if options.fields.is_empty() {
+        &default_fields
+    } else {
+        &options.fields
+    };
+
+    if options.format != OutputFormat::Table {
+        return print_rows(&mappings, active_fields, options, |m, f, o| m.field_value(f, o));
+    }
+
+    print_table(
+        mappings,
+        active_fields,
+        options,
+        |m, w, f, o, so| m.format_field(w, f, o, so),
+        |m| m.sizes,
+    )
+}
+
+fn validate_fields(options: &Options) -> Result<(), Error> {
+    if options.totals && options.format != OutputFormat::Table {
+        return Err(Error::Processing(
+            "--totals is not supported with --format csv/json".to_owned(),
+        ));
+    }
+    let invalid = options
+        .fields
+        .iter()
+        .chain(options.sort_field.iter())
+        .find(|f| !f.supported(options));
+    if let Some(field) = invalid {
+        return Err(Error::Processing(format!(
+            "Field `{}' is not supported in this mode",
+            field.name()
+        )));
+    }
+    Ok(())
+}
+
+fn run(mut options: Options) -> Result<(), Error> {
+    if let Some(ref dest) = options.capture {
+        return capture::capture(&options.source, dest);
+    }
+
+    validate_fields(&options)?;
+
+    let snapshot = if Snapshot::is_archive(&options.source) {
+        Some(Snapshot::load(&options.source)?)
+    } else {
+        None
+    };
+    let snapshot = snapshot.as_ref();
+
+    let (mem_total, swap_total) = read_meminfo_totals(&options.source, snapshot);
+    options.mem_total = mem_total;
+    options.swap_total = swap_total;
+    let options = &options;
+
+    let processes = all_processes(&options.source, snapshot);
     let mut filters = filter::Filters::new();
     if let Some(ref process) = options.process_filter {
         filters.process(process);
@@ -220,22 +542,47 @@ fn run(options: &Options) -> Result<(), Error> {
         filters.user(user);
     }
 
-    let mut process_details = processes
+    let users = all_users(snapshot);
+
+    if options.map {
+        let mut mappings = group_mappings(&processes, &filters, &users, snapshot);
+        let sort_field = options.sort_field.unwrap_or(Field::Pss);
+        if options.reverse {
+            mappings.sort_by(|m1, m2| m1.cmp_by(sort_field, m2, options).reverse());
+        } else {
+            mappings.sort_by(|m1, m2| m1.cmp_by(sort_field, m2, options));
+        }
+        return print_mappings(mappings, options);
+    }
+
+    let process_details = processes
         .par_iter()
         .filter(|p| {
             !p.cmdline.is_empty()
                 && (filters.accept_process(&p.command) || filters.accept_process(&p.cmdline))
         })
-        .filter_map(|p| get_process_details(p, &users).ok())
+        .filter_map(|p| get_process_details(p, &users, snapshot).ok())
         .filter(|d| filters.accept_user(d.user.name()))
         .collect::<Vec<_>>();
     let sort_field = options.sort_field.unwrap_or(Field::Rss);
-    if options.reverse {
-        process_details.sort_by(|p1, p2| p1.cmp_by(sort_field, p2, options).reverse());
+
+    if let Some(group_by) = options.group_by {
+        let mut groups = group_processes(process_details, group_by);
+        if options.reverse {
+            groups.sort_by(|g1, g2| g1.cmp_by(sort_field, g2, options).reverse());
+        } else {
+            groups.sort_by(|g1, g2| g1.cmp_by(sort_field, g2, options));
+        }
+        print_groups(groups, options)
     } else {
-        process_details.sort_by(|p1, p2| p1.cmp_by(sort_field, p2, options));
+        let mut process_details = process_details;
+        if options.reverse {
+            process_details.sort_by(|p1, p2| p1.cmp_by(sort_field, p2, options).reverse());
+        } else {
+            process_details.sort_by(|p1, p2| p1.cmp_by(sort_field, p2, options));
+        }
+        print_processes(process_details, options)
     }
-    print_processes(process_details, options)
 }
 
 fn disable_sigpipe_handling() {
@@ -248,7 +595,7 @@ fn main() {
     disable_sigpipe_handling();
 
     let options = Options::from_args();
-    match run(&options) {
+    match run(options) {
         Ok(_) => {}
         Err(e) => {
             eprintln!("{}", e);
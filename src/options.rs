@@ -2,6 +2,44 @@ use clap::{App, Arg};
 use std::path::PathBuf;
 use std::str::FromStr;
 
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Csv,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(OutputFormat::Table),
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("Unknown format: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum GroupBy {
+    User,
+    Command,
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user" => Ok(GroupBy::User),
+            "command" => Ok(GroupBy::Command),
+            _ => Err(format!("Unknown group: {}", s)),
+        }
+    }
+}
+
 pub struct Options {
     pub no_header: bool,
     pub process_filter: Option<String>,
@@ -13,6 +51,15 @@ pub struct Options {
     pub fields: Vec<super::fields::Field>,
     pub sort_field: Option<super::fields::Field>,
     pub totals: bool,
+    pub group_by: Option<GroupBy>,
+    /// Filled in by `main::run` once the `--source`/snapshot is resolved,
+    /// since reading `meminfo` may mean reading through a `--capture`d
+    /// archive rather than the filesystem.
+    pub mem_total: u64,
+    pub swap_total: u64,
+    pub map: bool,
+    pub format: OutputFormat,
+    pub capture: Option<PathBuf>,
 }
 
 impl Options {
@@ -88,7 +135,34 @@ impl Options {
                     .long("totals")
                     .about("Show totals"),
             )
+            .arg(
+                Arg::new("group-by")
+                    .long("group-by")
+                    .about("Group processes by user or command, summing their memory")
+                    .takes_value(true)
+                    .validator(|s| GroupBy::from_str(s)),
+            )
+            .arg(
+                Arg::new("map")
+                    .long("map")
+                    .about("Show memory use per mapped file/library instead of per process"),
+            )
+            .arg(
+                Arg::new("format")
+                    .long("format")
+                    .about("Output format")
+                    .takes_value(true)
+                    .default_value("table")
+                    .validator(|s| OutputFormat::from_str(s)),
+            )
+            .arg(
+                Arg::new("capture")
+                    .long("capture")
+                    .about("Capture a snapshot of the source into a tar archive and exit")
+                    .takes_value(true),
+            )
             .get_matches();
+        let source = matches.value_of_os("source").map(PathBuf::from).unwrap();
         Options {
             no_header: matches.is_present("no-header"),
             process_filter: matches.value_of("process-filter").map(|s| s.to_string()),
@@ -96,7 +170,7 @@ impl Options {
             numeric: matches.is_present("numeric"),
             reverse: matches.is_present("reverse"),
             abbreviate: matches.is_present("abbreviate"),
-            source: matches.value_of_os("source").map(PathBuf::from).unwrap(),
+            source,
             fields: matches.values_of("fields").map_or_else(Vec::new, |v| {
                 v.map(|s| FromStr::from_str(s).unwrap()).collect()
             }),
@@ -104,6 +178,16 @@ impl Options {
                 .value_of("sort-field")
                 .map(|s| FromStr::from_str(s).unwrap()),
             totals: matches.is_present("totals"),
+            group_by: matches
+                .value_of("group-by")
+                .map(|s| FromStr::from_str(s).unwrap()),
+            mem_total: 0,
+            swap_total: 0,
+            map: matches.is_present("map"),
+            format: matches
+                .value_of("format")
+                .map_or(OutputFormat::Table, |s| FromStr::from_str(s).unwrap()),
+            capture: matches.value_of_os("capture").map(PathBuf::from),
         }
     }
 }
@@ -0,0 +1,57 @@
+use std::io::{self, Write};
+
+use super::fields::{Field, FieldValue};
+
+pub fn csv_header<W: Write>(mut writer: W, fields: &[Field]) -> io::Result<()> {
+    let header = fields
+        .iter()
+        .map(|f| f.name())
+        .collect::<Vec<_>>()
+        .join(",");
+    writeln!(writer, "{}", header)
+}
+
+pub fn csv_row<W: Write>(mut writer: W, values: &[FieldValue]) -> io::Result<()> {
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            write!(writer, ",")?;
+        }
+        match value {
+            FieldValue::Text(s) => write!(writer, "\"{}\"", s.replace('"', "\"\""))?,
+            FieldValue::Integer(n) => write!(writer, "{}", n)?,
+            FieldValue::Float(n) => write!(writer, "{:.1}", n)?,
+        }
+    }
+    writeln!(writer)
+}
+
+pub fn json_row<W: Write>(mut writer: W, fields: &[Field], values: &[FieldValue]) -> io::Result<()> {
+    write!(writer, "{{")?;
+    for (i, (field, value)) in fields.iter().zip(values).enumerate() {
+        if i > 0 {
+            write!(writer, ",")?;
+        }
+        write!(writer, "\"{}\":", field.name())?;
+        match value {
+            FieldValue::Text(s) => write!(writer, "\"{}\"", json_escape(s))?,
+            FieldValue::Integer(n) => write!(writer, "{}", n)?,
+            FieldValue::Float(n) => write!(writer, "{}", n)?,
+        }
+    }
+    write!(writer, "}}")
+}
+
+fn json_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
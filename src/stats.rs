@@ -11,7 +11,7 @@ use std::ops::{Add, AddAssign};
 use std::path::PathBuf;
 
 use super::error::Error;
-use super::fields::Field;
+use super::fields::{Field, FieldValue};
 use super::options::Options;
 
 #[derive(Clone)]
@@ -46,11 +46,9 @@ impl ProcessDetails {
                     write!(writer, "{:10}", self.user.name().to_string_lossy())
                 }
             }
-            Field::Pss => self.sizes.pss.format_to(writer, opts, size_opts),
-            Field::Rss => self.sizes.rss.format_to(writer, opts, size_opts),
-            Field::Uss => self.sizes.uss.format_to(writer, opts, size_opts),
-            Field::Swap => self.sizes.swap.format_to(writer, opts, size_opts),
             Field::Cmdline => write!(writer, "{:10}", self.process.cmdline.to_string_lossy()),
+            Field::Count => panic!("Field not supported for processes: {}", field.name()),
+            _ => self.sizes.format_field(writer, field, opts, size_opts),
         }
     }
 
@@ -64,11 +62,119 @@ impl ProcessDetails {
                     self.user.name().cmp(other.user.name())
                 }
             }
-            Field::Pss => self.sizes.pss.cmp(&other.sizes.pss),
-            Field::Rss => self.sizes.rss.cmp(&other.sizes.rss),
-            Field::Uss => self.sizes.uss.cmp(&other.sizes.uss),
-            Field::Swap => self.sizes.swap.cmp(&other.sizes.swap),
             Field::Cmdline => self.process.cmdline.cmp(&other.process.cmdline),
+            _ => self.sizes.cmp_by(field, &other.sizes),
+        }
+    }
+
+    pub fn field_value(&self, field: Field, opts: &Options) -> FieldValue {
+        match field {
+            Field::Pid => FieldValue::Integer(self.process.pid as u64),
+            Field::User => {
+                if opts.numeric {
+                    FieldValue::Integer(self.user.uid() as u64)
+                } else {
+                    FieldValue::Text(self.user.name().to_string_lossy().into_owned())
+                }
+            }
+            Field::Cmdline => FieldValue::Text(self.process.cmdline.to_string_lossy().into_owned()),
+            Field::Count => panic!("Field not supported for processes: {}", field.name()),
+            _ => self.sizes.field_value(field, opts),
+        }
+    }
+}
+
+fn percent_of(value: usize, total: u64) -> f64 {
+    if total > 0 {
+        value as f64 / total as f64 * 100.0
+    } else {
+        0.0
+    }
+}
+
+fn format_percent<W: Write>(mut writer: W, value: usize, total: u64) -> io::Result<()> {
+    write!(writer, "{:>9.1}%", percent_of(value, total))
+}
+
+/// One row of a `--group-by` summary: a group key (user name or command),
+/// the number of processes folded into it, and their summed sizes.
+pub struct GroupDetails {
+    pub key: OsString,
+    pub count: usize,
+    pub sizes: ProcessSizes,
+}
+
+impl GroupDetails {
+    pub fn format_field<W: Write>(
+        &self,
+        mut writer: W,
+        field: Field,
+        opts: &Options,
+        size_opts: &FileSizeOpts,
+    ) -> io::Result<()> {
+        match field {
+            Field::User | Field::Cmdline => write!(writer, "{:10}", self.key.to_string_lossy()),
+            Field::Count => write!(writer, "{:10}", self.count),
+            _ => self.sizes.format_field(writer, field, opts, size_opts),
+        }
+    }
+
+    pub fn cmp_by(&self, field: Field, other: &Self, _opts: &Options) -> Ordering {
+        match field {
+            Field::User | Field::Cmdline => self.key.cmp(&other.key),
+            Field::Count => self.count.cmp(&other.count),
+            _ => self.sizes.cmp_by(field, &other.sizes),
+        }
+    }
+
+    pub fn field_value(&self, field: Field, opts: &Options) -> FieldValue {
+        match field {
+            Field::User | Field::Cmdline => {
+                FieldValue::Text(self.key.to_string_lossy().into_owned())
+            }
+            Field::Count => FieldValue::Integer(self.count as u64),
+            _ => self.sizes.field_value(field, opts),
+        }
+    }
+}
+
+/// One row of a `--map` summary: a mapped object (a backing file or a
+/// pseudo-mapping like `[heap]`), how many processes map it, and the
+/// summed sizes across those mappings.
+pub struct Mapping {
+    pub name: OsString,
+    pub mapper_count: usize,
+    pub sizes: ProcessSizes,
+}
+
+impl Mapping {
+    pub fn format_field<W: Write>(
+        &self,
+        mut writer: W,
+        field: Field,
+        opts: &Options,
+        size_opts: &FileSizeOpts,
+    ) -> io::Result<()> {
+        match field {
+            Field::Mapping => write!(writer, "{:10}", self.name.to_string_lossy()),
+            Field::Mappers => write!(writer, "{:10}", self.mapper_count),
+            _ => self.sizes.format_field(writer, field, opts, size_opts),
+        }
+    }
+
+    pub fn cmp_by(&self, field: Field, other: &Self, _opts: &Options) -> Ordering {
+        match field {
+            Field::Mapping => self.name.cmp(&other.name),
+            Field::Mappers => self.mapper_count.cmp(&other.mapper_count),
+            _ => self.sizes.cmp_by(field, &other.sizes),
+        }
+    }
+
+    pub fn field_value(&self, field: Field, opts: &Options) -> FieldValue {
+        match field {
+            Field::Mapping => FieldValue::Text(self.name.to_string_lossy().into_owned()),
+            Field::Mappers => FieldValue::Integer(self.mapper_count as u64),
+            _ => self.sizes.field_value(field, opts),
         }
     }
 }
@@ -100,6 +206,10 @@ impl Size {
             write!(writer, "{:10}", self.0)
         }
     }
+
+    pub fn bytes(&self) -> usize {
+        self.0
+    }
 }
 
 impl Default for Size {
@@ -113,6 +223,13 @@ pub struct ProcessSizes {
     pub pss: Size,
     pub uss: Size,
     pub swap: Size,
+    pub shared: Size,
+    pub pss_anon: Size,
+    pub pss_file: Size,
+    pub referenced: Size,
+    pub anonymous: Size,
+    pub locked: Size,
+    pub private_hugetlb: Size,
 }
 
 impl Add for Size {
@@ -142,9 +259,58 @@ impl ProcessSizes {
             Field::Rss => self.rss.format_to(writer, opts, size_opts),
             Field::Uss => self.uss.format_to(writer, opts, size_opts),
             Field::Swap => self.swap.format_to(writer, opts, size_opts),
+            Field::Shared => self.shared.format_to(writer, opts, size_opts),
+            Field::PssAnon => self.pss_anon.format_to(writer, opts, size_opts),
+            Field::PssFile => self.pss_file.format_to(writer, opts, size_opts),
+            Field::Referenced => self.referenced.format_to(writer, opts, size_opts),
+            Field::Anonymous => self.anonymous.format_to(writer, opts, size_opts),
+            Field::Locked => self.locked.format_to(writer, opts, size_opts),
+            Field::PrivateHugetlb => self.private_hugetlb.format_to(writer, opts, size_opts),
+            Field::PssPercent => format_percent(writer, self.pss.bytes(), opts.mem_total),
+            Field::RssPercent => format_percent(writer, self.rss.bytes(), opts.mem_total),
+            Field::SwapPercent => format_percent(writer, self.swap.bytes(), opts.swap_total),
             _ => panic!("Field not supported for totals: {}", field.name()),
         }
     }
+
+    pub fn cmp_by(&self, field: Field, other: &Self) -> Ordering {
+        match field {
+            Field::Pss | Field::PssPercent => self.pss.cmp(&other.pss),
+            Field::Rss | Field::RssPercent => self.rss.cmp(&other.rss),
+            Field::Uss => self.uss.cmp(&other.uss),
+            Field::Swap | Field::SwapPercent => self.swap.cmp(&other.swap),
+            Field::Shared => self.shared.cmp(&other.shared),
+            Field::PssAnon => self.pss_anon.cmp(&other.pss_anon),
+            Field::PssFile => self.pss_file.cmp(&other.pss_file),
+            Field::Referenced => self.referenced.cmp(&other.referenced),
+            Field::Anonymous => self.anonymous.cmp(&other.anonymous),
+            Field::Locked => self.locked.cmp(&other.locked),
+            Field::PrivateHugetlb => self.private_hugetlb.cmp(&other.private_hugetlb),
+            _ => panic!("Field not supported for sizes: {}", field.name()),
+        }
+    }
+
+    pub fn field_value(&self, field: Field, opts: &Options) -> FieldValue {
+        match field {
+            Field::Pss => FieldValue::Integer(self.pss.bytes() as u64),
+            Field::Rss => FieldValue::Integer(self.rss.bytes() as u64),
+            Field::Uss => FieldValue::Integer(self.uss.bytes() as u64),
+            Field::Swap => FieldValue::Integer(self.swap.bytes() as u64),
+            Field::Shared => FieldValue::Integer(self.shared.bytes() as u64),
+            Field::PssAnon => FieldValue::Integer(self.pss_anon.bytes() as u64),
+            Field::PssFile => FieldValue::Integer(self.pss_file.bytes() as u64),
+            Field::Referenced => FieldValue::Integer(self.referenced.bytes() as u64),
+            Field::Anonymous => FieldValue::Integer(self.anonymous.bytes() as u64),
+            Field::Locked => FieldValue::Integer(self.locked.bytes() as u64),
+            Field::PrivateHugetlb => FieldValue::Integer(self.private_hugetlb.bytes() as u64),
+            Field::PssPercent => FieldValue::Float(percent_of(self.pss.bytes(), opts.mem_total)),
+            Field::RssPercent => FieldValue::Float(percent_of(self.rss.bytes(), opts.mem_total)),
+            Field::SwapPercent => {
+                FieldValue::Float(percent_of(self.swap.bytes(), opts.swap_total))
+            }
+            _ => panic!("Field not supported for sizes: {}", field.name()),
+        }
+    }
 }
 
 impl Default for ProcessSizes {
@@ -154,6 +320,13 @@ impl Default for ProcessSizes {
             pss: Default::default(),
             uss: Default::default(),
             swap: Default::default(),
+            shared: Default::default(),
+            pss_anon: Default::default(),
+            pss_file: Default::default(),
+            referenced: Default::default(),
+            anonymous: Default::default(),
+            locked: Default::default(),
+            private_hugetlb: Default::default(),
         }
     }
 }
@@ -167,6 +340,13 @@ impl Add for ProcessSizes {
             pss: self.pss + other.pss,
             uss: self.uss + other.uss,
             swap: self.swap + other.swap,
+            shared: self.shared + other.shared,
+            pss_anon: self.pss_anon + other.pss_anon,
+            pss_file: self.pss_file + other.pss_file,
+            referenced: self.referenced + other.referenced,
+            anonymous: self.anonymous + other.anonymous,
+            locked: self.locked + other.locked,
+            private_hugetlb: self.private_hugetlb + other.private_hugetlb,
         }
     }
 }
@@ -177,5 +357,12 @@ impl AddAssign for ProcessSizes {
         self.pss += other.pss;
         self.uss += other.uss;
         self.swap += other.swap;
+        self.shared += other.shared;
+        self.pss_anon += other.pss_anon;
+        self.pss_file += other.pss_file;
+        self.referenced += other.referenced;
+        self.anonymous += other.anonymous;
+        self.locked += other.locked;
+        self.private_hugetlb += other.private_hugetlb;
     }
 }